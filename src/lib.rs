@@ -5,8 +5,9 @@
 use std::io::prelude::*;
 use std::io::BufReader;
 use std::fs::File;
+use std::path::Path;
 use std::collections::HashMap;
-use std::str::{FromStr, Split};
+use std::str::FromStr;
 
 /// A mesh for some model containing its triangle geometry
 /// This object could be a single polygon group or object within a file
@@ -18,17 +19,93 @@ pub struct Mesh {
     pub normals: Vec<f32>,
     pub texcoords: Vec<f32>,
     pub indices: Vec<u32>,
+    /// Index into the `Vec<Material>` returned alongside the models, or `None`
+    /// if the mesh had no `usemtl` applied to it or its `usemtl` named a material
+    /// that wasn't found in the loaded material libraries
+    pub material_id: Option<usize>,
 }
 
 impl Mesh {
     /// Create a new mesh specifying the geometry for the mesh
-    pub fn new(pos: Vec<f32>, norm: Vec<f32>, tex: Vec<f32>, indices: Vec<u32>) -> Mesh {
-        Mesh { positions: pos, normals: norm, texcoords: tex, indices: indices }
+    pub fn new(pos: Vec<f32>, norm: Vec<f32>, tex: Vec<f32>, indices: Vec<u32>, material_id: Option<usize>) -> Mesh {
+        Mesh { positions: pos, normals: norm, texcoords: tex, indices: indices, material_id: material_id }
     }
     /// Create a new empty mesh
     pub fn empty() -> Mesh {
-        Mesh { positions: Vec::new(), normals: Vec::new(), texcoords: Vec::new(), indices: Vec::new() }
+        Mesh { positions: Vec::new(), normals: Vec::new(), texcoords: Vec::new(), indices: Vec::new(), material_id: None }
     }
+    /// Generate smooth per-vertex normals for this mesh from its positions and indices,
+    /// overwriting any normals already present. Useful for OBJ files that omit `vn` data.
+    ///
+    /// Each triangle's face normal, weighted by its area, is accumulated onto the normal of
+    /// every vertex it touches and the result is normalized, so vertices shared between
+    /// triangles end up with the average of their neighboring face normals.
+    pub fn generate_normals(&mut self) {
+        let num_verts = self.positions.len() / 3;
+        let mut normals = vec![0.0f32; num_verts * 3];
+        for tri in self.indices.chunks(3) {
+            if tri.len() != 3 {
+                continue;
+            }
+            let a = tri[0] as usize;
+            let b = tri[1] as usize;
+            let c = tri[2] as usize;
+            let pa = [self.positions[a * 3], self.positions[a * 3 + 1], self.positions[a * 3 + 2]];
+            let pb = [self.positions[b * 3], self.positions[b * 3 + 1], self.positions[b * 3 + 2]];
+            let pc = [self.positions[c * 3], self.positions[c * 3 + 1], self.positions[c * 3 + 2]];
+            let e1 = [pb[0] - pa[0], pb[1] - pa[1], pb[2] - pa[2]];
+            let e2 = [pc[0] - pa[0], pc[1] - pa[1], pc[2] - pa[2]];
+            // Unnormalized cross product so that larger faces contribute more weight
+            let face_normal = [
+                e1[1] * e2[2] - e1[2] * e2[1],
+                e1[2] * e2[0] - e1[0] * e2[2],
+                e1[0] * e2[1] - e1[1] * e2[0],
+            ];
+            for &v in &[a, b, c] {
+                normals[v * 3] += face_normal[0];
+                normals[v * 3 + 1] += face_normal[1];
+                normals[v * 3 + 2] += face_normal[2];
+            }
+        }
+        for n in normals.chunks_mut(3) {
+            let len = (n[0] * n[0] + n[1] * n[1] + n[2] * n[2]).sqrt();
+            // Guard against degenerate faces (zero-area triangles) leaving a zero-length normal
+            if len > 0.0 {
+                n[0] /= len;
+                n[1] /= len;
+                n[2] /= len;
+            }
+        }
+        self.normals = normals;
+    }
+    /// Compute the axis-aligned bounding box of this mesh's positions, or `None` if it has none
+    pub fn compute_bounds(&self) -> Option<Aabb> {
+        if self.positions.is_empty() {
+            return None;
+        }
+        let mut bounds = Aabb {
+            min: [self.positions[0], self.positions[1], self.positions[2]],
+            max: [self.positions[0], self.positions[1], self.positions[2]],
+        };
+        for p in self.positions.chunks(3) {
+            for i in 0..3 {
+                if p[i] < bounds.min[i] {
+                    bounds.min[i] = p[i];
+                }
+                if p[i] > bounds.max[i] {
+                    bounds.max[i] = p[i];
+                }
+            }
+        }
+        Some(bounds)
+    }
+}
+
+/// An axis-aligned bounding box, as computed by `Mesh::compute_bounds`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Aabb {
+    pub min: [f32; 3],
+    pub max: [f32; 3],
 }
 
 /// A named model within the file
@@ -46,6 +123,49 @@ impl Model {
     }
 }
 
+/// A material that can be applied to a `Mesh` via its `material_id`, parsed out
+/// of an MTL file referenced by an OBJ's `mtllib` statement
+#[derive(Debug, Clone)]
+pub struct Material {
+    pub name: String,
+    pub ambient: [f32; 3],
+    pub diffuse: [f32; 3],
+    pub specular: [f32; 3],
+    pub shininess: f32,
+    pub dissolve: f32,
+    pub optical_density: f32,
+    pub illumination_model: Option<u8>,
+    pub ambient_texture: String,
+    pub diffuse_texture: String,
+    pub specular_texture: String,
+    pub normal_texture: String,
+    pub dissolve_texture: String,
+    /// Any `key value` pairs in the material that aren't recognized above are
+    /// stored here so callers can still make use of them
+    pub unknown_param: HashMap<String, String>,
+}
+
+impl Material {
+    /// Create a new empty material with the given name, all other properties defaulted
+    pub fn empty(name: String) -> Material {
+        Material {
+            name: name,
+            ambient: [0.0; 3],
+            diffuse: [0.0; 3],
+            specular: [0.0; 3],
+            shininess: 0.0,
+            dissolve: 1.0,
+            optical_density: 1.0,
+            illumination_model: None,
+            ambient_texture: String::new(),
+            diffuse_texture: String::new(),
+            specular_texture: String::new(),
+            normal_texture: String::new(),
+            dissolve_texture: String::new(),
+            unknown_param: HashMap::new(),
+        }
+    }
+}
 
 /// TODO: Decide on various errors we'll return
 #[derive(Debug)]
@@ -57,13 +177,18 @@ pub enum LoadError {
     NormalParseError,
     TexcoordParseError,
     FaceParseError,
+    MaterialParseError,
     InvalidObjectName,
     GenericFailure,
 }
 
-/// LoadResult is a result containing all the models loaded from the file or any
-/// error that occured while loading
-pub type LoadResult = Result<Vec<Model>, LoadError>;
+/// LoadResult is a result containing all the models and materials loaded from the
+/// file or any error that occured while loading
+pub type LoadResult = Result<(Vec<Model>, Vec<Material>), LoadError>;
+
+/// MTLLoadResult is a result containing all the materials loaded from the file
+/// or any error that occured while loading
+pub type MTLLoadResult = Result<Vec<Material>, LoadError>;
 
 /// Struct storing indices corresponding to the vertex
 /// Some vertices may not have texcoords or normals, 0 is used to indicate this
@@ -87,8 +212,8 @@ impl VertexIndices {
             // Catch case of v//vn where we'll find an empty string in one of our splits
             // since there are no texcoords for the mesh
             if !i.1.is_empty() {
-                match isize::from_str(i.1) {
-                    Ok(x) => {
+                match parse_int_fast(i.1) {
+                    Some(x) => {
                         // Handle relative indices
                         indices[i.0] =
                             if x < 0 {
@@ -102,7 +227,7 @@ impl VertexIndices {
                                 (x - 1) as usize
                             };
                     },
-                    Err(_) => return None,
+                    None => return None,
                 }
             }
         }
@@ -110,24 +235,113 @@ impl VertexIndices {
     }
 }
 
-/// Enum representing either a quad or triangle face, storing indices for the face vertices
-#[derive(Debug)]
-enum Face {
-    Triangle(VertexIndices, VertexIndices, VertexIndices),
-    Quad(VertexIndices, VertexIndices, VertexIndices, VertexIndices)
+/// Parse a signed integer directly from the bytes of `s`, without allocating or going
+/// through `FromStr`. Handles an optional leading `+`/`-` sign. Returns `None` if `s`
+/// isn't a valid run of ASCII digits.
+fn parse_int_fast(s: &str) -> Option<isize> {
+    let bytes = s.as_bytes();
+    let mut i = 0;
+    let mut sign = 1isize;
+    if let Some(&b) = bytes.get(i) {
+        if b == b'-' { sign = -1; i += 1; } else if b == b'+' { i += 1; }
+    }
+    let mut value: isize = 0;
+    let mut any_digits = false;
+    while let Some(&b) = bytes.get(i) {
+        if !b.is_ascii_digit() {
+            break;
+        }
+        value = value * 10 + (b - b'0') as isize;
+        i += 1;
+        any_digits = true;
+    }
+    if !any_digits || i != bytes.len() {
+        return None;
+    }
+    Some(sign * value)
+}
+
+/// Parse a floating point number directly from the bytes of `s`, without allocating or
+/// going through `FromStr`. Handles an optional leading sign, a single decimal point and
+/// an optional exponent (`e`/`E`, with its own optional sign).
+fn parse_float_fast(s: &str) -> Option<f32> {
+    let bytes = s.as_bytes();
+    let mut i = 0;
+    let mut sign = 1.0f64;
+    if let Some(&b) = bytes.get(i) {
+        if b == b'-' { sign = -1.0; i += 1; } else if b == b'+' { i += 1; }
+    }
+    let mut mantissa = 0.0f64;
+    let mut any_digits = false;
+    while let Some(&b) = bytes.get(i) {
+        if !b.is_ascii_digit() {
+            break;
+        }
+        mantissa = mantissa * 10.0 + (b - b'0') as f64;
+        i += 1;
+        any_digits = true;
+    }
+    if let Some(&b'.') = bytes.get(i) {
+        i += 1;
+        let mut frac_scale = 0.1;
+        while let Some(&b) = bytes.get(i) {
+            if !b.is_ascii_digit() {
+                break;
+            }
+            mantissa += (b - b'0') as f64 * frac_scale;
+            frac_scale *= 0.1;
+            i += 1;
+            any_digits = true;
+        }
+    }
+    if !any_digits {
+        return None;
+    }
+    let mut exponent = 0i32;
+    if let Some(&b) = bytes.get(i) {
+        if b == b'e' || b == b'E' {
+            i += 1;
+            let mut exp_sign = 1i32;
+            if let Some(&b) = bytes.get(i) {
+                if b == b'-' { exp_sign = -1; i += 1; } else if b == b'+' { i += 1; }
+            }
+            let mut any_exp_digits = false;
+            while let Some(&b) = bytes.get(i) {
+                if !b.is_ascii_digit() {
+                    break;
+                }
+                exponent = exponent * 10 + (b - b'0') as i32;
+                i += 1;
+                any_exp_digits = true;
+            }
+            if !any_exp_digits {
+                return None;
+            }
+            exponent *= exp_sign;
+        }
+    }
+    if i != bytes.len() {
+        return None;
+    }
+    Some((sign * mantissa * 10f64.powi(exponent)) as f32)
 }
 
+/// A polygonal face, storing the indices for each of its vertices in order around the polygon.
+/// Faces with more than 3 vertices are triangulated with a fan when exported to a `Mesh`.
+#[derive(Debug)]
+struct Face(Vec<VertexIndices>);
+
 /// Parse the floatn information from the words, words is an iterator over the float strings
 /// Returns false if parsing failed
-fn parse_floatn(val_str: Split<char>, vals: &mut Vec<f32>, n: usize) -> bool {
+fn parse_floatn<'a, I: Iterator<Item=&'a str>>(val_str: I, vals: &mut Vec<f32>, n: usize) -> bool {
     let sz = vals.len();
     for p in val_str {
         if p.is_empty() {
             continue;
         }
-        match FromStr::from_str(p.trim()) {
-            Ok(x) => vals.push(x),
-            Err(_) => return false,
+        match parse_float_fast(p) {
+            Some(x) => vals.push(x),
+            None => return false,
         }
     }
     // Require that we found the desired number of floats
@@ -138,7 +352,7 @@ fn parse_floatn(val_str: Split<char>, vals: &mut Vec<f32>, n: usize) -> bool {
 /// Also handles relative face indices (negative values) which is why passing the number of
 /// positions, texcoords and normals is required
 /// returns false if an error occured parsing the face
-fn parse_face(face_str: Split<char>, faces: &mut Vec<Face>, pos_sz: usize, tex_sz: usize, norm_sz: usize) -> bool {
+fn parse_face<'a, I: Iterator<Item=&'a str>>(face_str: I, faces: &mut Vec<Face>, pos_sz: usize, tex_sz: usize, norm_sz: usize) -> bool {
     let mut indices = Vec::new();
     for f in face_str {
         match VertexIndices::parse(f, pos_sz, tex_sz, norm_sz) {
@@ -146,12 +360,11 @@ fn parse_face(face_str: Split<char>, faces: &mut Vec<Face>, pos_sz: usize, tex_s
             None => return false,
         }
     }
-    // Check if we read a triangle or a quad face and push it on
-    match indices.len() {
-        3 => faces.push(Face::Triangle(indices[0], indices[1], indices[2])),
-        4 => faces.push(Face::Quad(indices[0], indices[1], indices[2], indices[3])),
-        _ => return false,
+    // A face needs at least 3 vertices to be a valid polygon
+    if indices.len() < 3 {
+        return false;
     }
+    faces.push(Face(indices));
     true
 }
 
@@ -183,129 +396,310 @@ fn add_vertex(mesh: &mut Mesh, index_map: &mut HashMap<VertexIndices, u32>, vert
 }
 
 /// Export a list of faces to a mesh and return it, converting quads to tris
-fn export_faces(pos: &Vec<f32>, texcoord: &Vec<f32>, normal: &Vec<f32>, faces: &Vec<Face>) -> Mesh {
+fn export_faces(pos: &Vec<f32>, texcoord: &Vec<f32>, normal: &Vec<f32>, faces: &Vec<Face>,
+                 material_id: Option<usize>) -> Mesh {
     let mut index_map = HashMap::new();
     let mut mesh = Mesh::empty();
+    mesh.material_id = material_id;
     // TODO: When drain becomes stable we should use that, since we clear `faces` later anyway
     for f in faces {
-        match *f {
-            Face::Triangle(ref a, ref b, ref c) => {
-                add_vertex(&mut mesh, &mut index_map, a, pos, texcoord, normal);
-                add_vertex(&mut mesh, &mut index_map, b, pos, texcoord, normal);
-                add_vertex(&mut mesh, &mut index_map, c, pos, texcoord, normal);
-            },
-            Face::Quad(ref a, ref b, ref c, ref d) => {
-                add_vertex(&mut mesh, &mut index_map, a, pos, texcoord, normal);
-                add_vertex(&mut mesh, &mut index_map, b, pos, texcoord, normal);
-                add_vertex(&mut mesh, &mut index_map, c, pos, texcoord, normal);
-
-                add_vertex(&mut mesh, &mut index_map, a, pos, texcoord, normal);
-                add_vertex(&mut mesh, &mut index_map, c, pos, texcoord, normal);
-                add_vertex(&mut mesh, &mut index_map, d, pos, texcoord, normal);
-            }
+        // Triangulate the polygon with a fan rooted at its first vertex: (v0, v1, v2),
+        // (v0, v2, v3), ... (v0, v_{n-2}, v_{n-1})
+        let verts = &f.0;
+        for i in 1..verts.len() - 1 {
+            add_vertex(&mut mesh, &mut index_map, &verts[0], pos, texcoord, normal);
+            add_vertex(&mut mesh, &mut index_map, &verts[i], pos, texcoord, normal);
+            add_vertex(&mut mesh, &mut index_map, &verts[i + 1], pos, texcoord, normal);
         }
     }
     mesh
 }
 
-/// Load the various meshes in an OBJ file
+/// Find the index of the material with the given name, if it was loaded from the mtllib
+fn find_material(materials: &Vec<Material>, name: &str) -> Option<usize> {
+    materials.iter().position(|m| m.name == name)
+}
+
+/// Build the name for a model given the current object name and group name, so that
+/// groups contribute to the name of the meshes they split off from an object
+fn model_name(name: &str, group: &str) -> String {
+    if name.is_empty() {
+        group.to_string()
+    } else if group.is_empty() {
+        name.to_string()
+    } else {
+        format!("{}_{}", name, group)
+    }
+}
+
+/// Load the various meshes and materials referenced in an OBJ file
 pub fn load_obj(file_name: &str) -> LoadResult {
-    println!("Loading file {}", file_name);
     let file = match File::open(file_name) {
         Ok(f) => f,
-        Err(e) => {
-            println!("tobj::load_obj - failed to open {} due to {}", file_name, e);
-            return Err(LoadError::OpenFileFailed);
-        },
+        Err(_) => return Err(LoadError::OpenFileFailed),
     };
     let mut reader = BufReader::new(file);
-    load_obj_buf(&mut reader)
+    let material_dir = Path::new(file_name).parent();
+    load_obj_buf(&mut reader, material_dir)
 }
 
-/// Load the various meshes in an OBJ buffer
-pub fn load_obj_buf<B: BufRead>(reader: &mut B) -> LoadResult {
+/// Load the various meshes and materials in an OBJ buffer, resolving any `mtllib`
+/// referenced material file relative to `material_dir`
+pub fn load_obj_buf<B: BufRead>(reader: &mut B, material_dir: Option<&Path>) -> LoadResult {
     let mut models = Vec::new();
+    let mut materials = Vec::new();
     let mut tmp_pos = Vec::new();
     let mut tmp_texcoord = Vec::new();
     let mut tmp_normal = Vec::new();
     let mut tmp_faces: Vec<Face> = Vec::new();
     // name of the current object being parsed
     let mut name = String::new();
-    // Next index for a new face we might find
+    // name of the current group, if any `g` statement has been seen since the last `o`
+    let mut group = String::new();
+    // index of the material currently active via `usemtl`
+    let mut mat_id = None;
     for line in reader.lines() {
-        // We just need the line for debugging for a bit
-        // TODO: Switch back to using `words` when it becomes stable
-        let (line, mut words) = match line {
-            Ok(ref line) => (&line[..], line[..].trim().split(' ')),
-            Err(e) => {
-                println!("tobj::load_obj - failed to read line due to {}", e);
-                return Err(LoadError::ReadError);
-            },
+        let line = match line {
+            Ok(line) => line,
+            Err(_) => return Err(LoadError::ReadError),
         };
+        let mut words = line.split_ascii_whitespace();
         match words.next() {
-            Some("#") => { println!("Skipping comment"); continue; },
+            Some("#") => continue,
             Some("v") => {
-                println!("Will parse vertex {}", line);
                 if !parse_floatn(words, &mut tmp_pos, 3) {
-                    println!("Failed to parse 'v'");
                     return Err(LoadError::PositionParseError);
                 }
             },
             Some("vt") => {
-                println!("Will parse texcoord {}", line);
                 if !parse_floatn(words, &mut tmp_texcoord, 2) {
                     return Err(LoadError::TexcoordParseError);
                 }
             },
             Some("vn") => {
-                println!("Will parse normal {}", line);
                 if !parse_floatn(words, &mut tmp_normal, 3) {
                     return Err(LoadError::NormalParseError);
                 }
             },
             Some("f") => {
-                println!("Will parse face {}", line);
                 if !parse_face(words, &mut tmp_faces, tmp_pos.len() / 3, tmp_texcoord.len() / 2, tmp_normal.len() / 3) {
                     return Err(LoadError::FaceParseError);
                 }
             },
             Some("o") => {
-                // If we were already parsing an object then a new object name
-                // signals the end of the current one, so push it onto our list of objects
-                if !name.is_empty() && !tmp_faces.is_empty() {
-                    models.push(Model::new(export_faces(&tmp_pos, &tmp_texcoord, &tmp_normal, &tmp_faces), name));
-                    println!("Finished parsing {:?}", models[models.len() - 1]);
+                // If we'd already accumulated faces (for the prior object, or for a file with
+                // no `o` at all) then a new object name signals the end of the current mesh
+                if !tmp_faces.is_empty() {
+                    let model_name = model_name(&name, &group);
+                    models.push(Model::new(export_faces(&tmp_pos, &tmp_texcoord, &tmp_normal, &tmp_faces, mat_id), model_name));
                     tmp_faces.clear();
                 }
+                group.clear();
                 match words.next() {
                     Some(n) => name = n.to_string(),
                     None => return Err(LoadError::InvalidObjectName),
                 }
-                println!("Beginning to parse new object: {}", name);
             },
-            Some("g") => { println!("Will parse group {}", line); },
-            Some("mtllib") => { println!("Will parse material lib {}", line); },
-            Some("usemtl") => { println!("Will parse usemtl {}", line); },
-            None => { println!("Skipping empty line"); continue; },
+            Some("g") => {
+                // A new group while we have pending faces means the current mesh is done,
+                // since it's segmented by group just like it is by `usemtl`
+                if !tmp_faces.is_empty() {
+                    let model_name = model_name(&name, &group);
+                    models.push(Model::new(export_faces(&tmp_pos, &tmp_texcoord, &tmp_normal, &tmp_faces, mat_id), model_name));
+                    tmp_faces.clear();
+                }
+                group = words.next().map_or(String::new(), |n| n.to_string());
+            },
+            Some("mtllib") => {
+                match words.next() {
+                    Some(lib) => {
+                        let mtl_path = match material_dir {
+                            Some(dir) => dir.join(lib),
+                            None => Path::new(lib).to_path_buf(),
+                        };
+                        materials.extend(load_mtl(&mtl_path)?);
+                    },
+                    None => return Err(LoadError::MaterialParseError),
+                }
+            },
+            Some("usemtl") => {
+                // A new `usemtl` while we have pending faces means the triangles seen so far
+                // belong to the material that was active, so finalize them into their own mesh
+                if !tmp_faces.is_empty() {
+                    let model_name = model_name(&name, &group);
+                    models.push(Model::new(export_faces(&tmp_pos, &tmp_texcoord, &tmp_normal, &tmp_faces, mat_id), model_name));
+                    tmp_faces.clear();
+                }
+                match words.next() {
+                    Some(mat_name) => mat_id = find_material(&materials, mat_name),
+                    None => mat_id = None,
+                }
+            },
+            None => continue,
             // TODO: throw error on unrecognized character. Currently with split we get a newline
             // and incorrectly through so this is off temporarily. Blocked until `words` becomes
             // stable
-            Some(c) => { println!("Unrecognized character: {}", c); /*return Err(LoadError::UnrecognizedCharacter) */ },
+            Some(_) => { /*return Err(LoadError::UnrecognizedCharacter)*/ },
         }
     }
-    // For the last object in the file we won't encounter another object name to tell us when it's
-    // done, so if we're parsing an object push the last one on the list as well
-    if !name.is_empty() {
-        models.push(Model::new(export_faces(&tmp_pos, &tmp_texcoord, &tmp_normal, &tmp_faces), name));
+    // For the last object/group in the file we won't encounter another object, group or usemtl
+    // to tell us when it's done, so push whatever's left accumulated onto the list as well
+    if !tmp_faces.is_empty() {
+        let model_name = model_name(&name, &group);
+        models.push(Model::new(export_faces(&tmp_pos, &tmp_texcoord, &tmp_normal, &tmp_faces, mat_id), model_name));
     }
-    for m in &models {
-        println!("Parsed Model: {:?}", m);
+    Ok((models, materials))
+}
+
+/// Load the material descriptions referenced in an MTL file
+pub fn load_mtl<P: AsRef<Path>>(file_name: P) -> MTLLoadResult {
+    let file_name = file_name.as_ref();
+    let file = match File::open(file_name) {
+        Ok(f) => f,
+        Err(_) => return Err(LoadError::OpenFileFailed),
+    };
+    let mut reader = BufReader::new(file);
+    load_mtl_buf(&mut reader)
+}
+
+/// Load the material descriptions in an MTL buffer
+pub fn load_mtl_buf<B: BufRead>(reader: &mut B) -> MTLLoadResult {
+    let mut materials = Vec::new();
+    // The material we're currently filling in, if any
+    let mut cur_mat: Option<Material> = None;
+    for line in reader.lines() {
+        let line = match line {
+            Ok(line) => line,
+            Err(_) => return Err(LoadError::ReadError),
+        };
+        let mut words = line.split_ascii_whitespace();
+        match words.next() {
+            Some("#") => continue,
+            Some("newmtl") => {
+                if let Some(m) = cur_mat.take() {
+                    materials.push(m);
+                }
+                match words.next() {
+                    Some(n) => cur_mat = Some(Material::empty(n.to_string())),
+                    None => return Err(LoadError::MaterialParseError),
+                }
+            },
+            Some("Ka") => {
+                if !parse_color(words, &mut cur_mat, |m| &mut m.ambient) {
+                    return Err(LoadError::MaterialParseError);
+                }
+            },
+            Some("Kd") => {
+                if !parse_color(words, &mut cur_mat, |m| &mut m.diffuse) {
+                    return Err(LoadError::MaterialParseError);
+                }
+            },
+            Some("Ks") => {
+                if !parse_color(words, &mut cur_mat, |m| &mut m.specular) {
+                    return Err(LoadError::MaterialParseError);
+                }
+            },
+            Some("Ns") => {
+                if !parse_scalar(words, &mut cur_mat, |m| &mut m.shininess) {
+                    return Err(LoadError::MaterialParseError);
+                }
+            },
+            Some("d") => {
+                if !parse_scalar(words, &mut cur_mat, |m| &mut m.dissolve) {
+                    return Err(LoadError::MaterialParseError);
+                }
+            },
+            Some("Ni") => {
+                if !parse_scalar(words, &mut cur_mat, |m| &mut m.optical_density) {
+                    return Err(LoadError::MaterialParseError);
+                }
+            },
+            Some("illum") => {
+                let m = match cur_mat {
+                    Some(ref mut m) => m,
+                    None => return Err(LoadError::MaterialParseError),
+                };
+                match words.next().and_then(|v| u8::from_str(v).ok()) {
+                    Some(v) => m.illumination_model = Some(v),
+                    None => return Err(LoadError::MaterialParseError),
+                }
+            },
+            Some("map_Ka") => {
+                set_texture(words, &mut cur_mat, |m| &mut m.ambient_texture);
+            },
+            Some("map_Kd") => {
+                set_texture(words, &mut cur_mat, |m| &mut m.diffuse_texture);
+            },
+            Some("map_Ks") => {
+                set_texture(words, &mut cur_mat, |m| &mut m.specular_texture);
+            },
+            Some("map_Bump") | Some("map_bump") | Some("bump") => {
+                set_texture(words, &mut cur_mat, |m| &mut m.normal_texture);
+            },
+            Some("map_d") => {
+                set_texture(words, &mut cur_mat, |m| &mut m.dissolve_texture);
+            },
+            None => continue,
+            Some(key) => {
+                let m = match cur_mat {
+                    Some(ref mut m) => m,
+                    None => continue,
+                };
+                let rest: Vec<&str> = words.collect();
+                m.unknown_param.insert(key.to_string(), rest.join(" "));
+            },
+        }
+    }
+    if let Some(m) = cur_mat.take() {
+        materials.push(m);
     }
-    Ok(models)
+    Ok(materials)
 }
 
-/// Print out all loaded properties of some models and associated materials (once mats are added)
-fn print_model_info(models: &Vec<Model>) {
+/// Parse an `Rgb`-like triple of floats (e.g. `Ka`/`Kd`/`Ks`) into the field selected by `field`
+fn parse_color<'a, I: Iterator<Item=&'a str>, F: Fn(&mut Material) -> &mut [f32; 3]>(val_str: I, cur_mat: &mut Option<Material>,
+                                                        field: F) -> bool {
+    let m = match *cur_mat {
+        Some(ref mut m) => m,
+        None => return false,
+    };
+    let mut vals = Vec::new();
+    if !parse_floatn(val_str, &mut vals, 3) {
+        return false;
+    }
+    let dst = field(m);
+    dst[0] = vals[0];
+    dst[1] = vals[1];
+    dst[2] = vals[2];
+    true
+}
+
+/// Parse a single float scalar (e.g. `Ns`/`d`/`Ni`) into the field selected by `field`
+fn parse_scalar<'a, I: Iterator<Item=&'a str>, F: Fn(&mut Material) -> &mut f32>(mut val_str: I, cur_mat: &mut Option<Material>,
+                                                   field: F) -> bool {
+    let m = match *cur_mat {
+        Some(ref mut m) => m,
+        None => return false,
+    };
+    match val_str.next().and_then(parse_float_fast) {
+        Some(v) => { *field(m) = v; true },
+        None => false,
+    }
+}
+
+/// Record a texture map path (e.g. `map_Kd`) into the field selected by `field`,
+/// ignoring the line if there's no material currently being parsed
+fn set_texture<'a, I: Iterator<Item=&'a str>, F: Fn(&mut Material) -> &mut String>(mut val_str: I, cur_mat: &mut Option<Material>, field: F) {
+    if let Some(ref mut m) = *cur_mat {
+        if let Some(path) = val_str.next() {
+            *field(m) = path.to_string();
+        }
+    }
+}
+
+/// Print out the names, indices and positions of some models and the name of the
+/// material each one uses
+fn print_model_info(models: &Vec<Model>, materials: &Vec<Material>) {
     println!("# of models: {}", models.len());
     for (i, m) in models.iter().enumerate() {
         let mesh = &m.mesh;
@@ -321,22 +715,23 @@ fn print_model_info(models: &Vec<Model>) {
         for v in 0..(mesh.positions.len() / 3) {
             println!("  v[{}] = ({}, {}, {})", v, mesh.positions[3 * v], mesh.positions[3 * v + 1], mesh.positions[3 * v + 2]);
         }
-        // TODO: loop through and print all materials
+
+        if let Some(id) = mesh.material_id {
+            println!("model[{}].material = {}", i, materials[id].name);
+        }
     }
 }
 
 #[test]
 fn test_tri(){
-    let triangle = load_obj("triangle.obj");
-    assert!(triangle.is_ok());
-    print_model_info(&triangle.unwrap());
+    let (models, materials) = load_obj("triangle.obj").unwrap();
+    print_model_info(&models, &materials);
 }
 
 #[test]
 fn test_quad(){
-    let quad = load_obj("quad.obj");
-    assert!(quad.is_ok());
-    print_model_info(&quad.unwrap());
+    let (models, materials) = load_obj("quad.obj").unwrap();
+    print_model_info(&models, &materials);
 }
 
 